@@ -1,88 +1,673 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use hmac::{Hmac, Mac};
 use ring::{aead, rand};
 use ring::aead::{LessSafeKey, UnboundKey, Nonce};
+use ring::rand::SecureRandom;
 use serde::{Serialize, Deserialize};
+use sha2::Sha256;
 use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
+use std::fs::File;
 use std::io::{Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
 use rpassword::read_password;
 use base64::{encode, decode};
 
+type HmacSha256 = Hmac<Sha256>;
+
 const STORAGE_FILE: &str = "passwords.enc";
 
+// Magic bytes identifying the salted-Argon2id header. Files written before
+// this format was introduced lack this prefix entirely, which is how
+// `load_passwords` tells them apart from legacy SHA-256 vaults.
+const HEADER_MAGIC: &[u8; 4] = b"PMK1";
+const HEADER_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+
+// Moderate-cost defaults: ~64 MiB / 3 passes / single lane. Callers that
+// want to ratchet these up on next save can build a custom `KdfParams`.
+const DEFAULT_MEM_KIB: u32 = 65536;
+const DEFAULT_ITERATIONS: u32 = 3;
+const DEFAULT_PARALLELISM: u32 = 1;
+
 #[derive(Serialize, Deserialize)]
 struct PasswordStore {
-    passwords: HashMap<String, String>,
+    passwords: HashMap<String, PasswordEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct PasswordEntry {
+    username: String,
+    password: String,
+    url: Option<String>,
+    notes: Option<String>,
+    created_at: u64,
+    modified_at: u64,
+}
+
+fn current_unix_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+#[derive(Clone, Copy)]
+struct KdfParams {
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: DEFAULT_MEM_KIB,
+            iterations: DEFAULT_ITERATIONS,
+            parallelism: DEFAULT_PARALLELISM,
+        }
+    }
+}
+
+// Header layout written ahead of the ciphertext in `passwords.enc`:
+//   magic (4) | version (1) | salt (16) | memory_kib (4 LE) | iterations (4 LE) | parallelism (4 LE)
+#[derive(Clone, Copy)]
+struct VaultHeader {
+    salt: [u8; SALT_LEN],
+    params: KdfParams,
 }
 
-fn generate_key_from_password(password: &str) -> [u8; 32] {
-    // In real-world, use a key derivation function like PBKDF2
-    // For simplicity, here we just hash the password (not recommended for real apps)
+impl VaultHeader {
+    fn generate(params: KdfParams) -> Self {
+        let rng = rand::SystemRandom::new();
+        let mut salt = [0u8; SALT_LEN];
+        rng.fill(&mut salt).expect("failed to generate salt");
+        VaultHeader { salt, params }
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(4 + 1 + SALT_LEN + 12);
+        out.extend_from_slice(HEADER_MAGIC);
+        out.push(HEADER_VERSION);
+        out.extend_from_slice(&self.salt);
+        out.extend_from_slice(&self.params.memory_kib.to_le_bytes());
+        out.extend_from_slice(&self.params.iterations.to_le_bytes());
+        out.extend_from_slice(&self.params.parallelism.to_le_bytes());
+        out
+    }
+
+    // Parses the header from the front of `data`, returning the header and
+    // the remaining ciphertext. Returns `None` if `data` doesn't start with
+    // `HEADER_MAGIC`, which signals a legacy SHA-256 vault.
+    fn parse(data: &[u8]) -> Option<(Self, &[u8])> {
+        let header_len = 4 + 1 + SALT_LEN + 12;
+        if data.len() < header_len || &data[0..4] != HEADER_MAGIC {
+            return None;
+        }
+        let version = data[4];
+        if version != HEADER_VERSION {
+            return None;
+        }
+        let mut salt = [0u8; SALT_LEN];
+        salt.copy_from_slice(&data[5..5 + SALT_LEN]);
+        let mut offset = 5 + SALT_LEN;
+        let memory_kib = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        offset += 4;
+        let iterations = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        offset += 4;
+        let parallelism = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        offset += 4;
+        let header = VaultHeader {
+            salt,
+            params: KdfParams { memory_kib, iterations, parallelism },
+        };
+        Some((header, &data[offset..]))
+    }
+}
+
+// Returns `Err` instead of panicking on bad parameters so a corrupted or
+// hand-edited vault header can't crash the process; callers that already
+// know their params are well-formed (freshly generated via `KdfParams::default`)
+// can still `.expect()` the result.
+fn generate_key_from_password(password: &str, salt: &[u8], params: KdfParams) -> Result<[u8; 32], String> {
+    let argon2_params = Params::new(params.memory_kib, params.iterations, params.parallelism, Some(32))
+        .map_err(|e| e.to_string())?;
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, argon2_params);
+    let mut key = [0u8; 32];
+    argon2
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+// Legacy key derivation kept only so vaults written before the Argon2id
+// header existed can still be opened. New saves never use this path.
+fn generate_key_from_password_legacy(password: &str) -> [u8; 32] {
     let digest = ring::digest::digest(&ring::digest::SHA256, password.as_bytes());
     let mut key = [0u8; 32];
     key.copy_from_slice(digest.as_ref());
     key
 }
 
-fn encrypt_data(key_bytes: &[u8], data: &[u8]) -> Vec<u8> {
-    let unbound_key = UnboundKey::new(&aead::AES_256_GCM, key_bytes).unwrap();
+// Legacy decrypt kept alongside `generate_key_from_password_legacy` so vaults
+// written before this series (single-shot AEAD, hardcoded all-zero nonce, no
+// stream framing) can still be opened. This must NOT be replaced by the
+// STREAM-framed `decrypt_data`/`decrypt_reader_to_writer` below: those expect
+// a random base nonce and length-prefixed blocks that a pre-series vault
+// simply doesn't have.
+fn decrypt_data_legacy(key_bytes: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    let unbound_key = UnboundKey::new(&aead::AES_256_GCM, key_bytes).ok()?;
     let key = LessSafeKey::new(unbound_key);
-    let nonce_bytes = [0u8; 12]; // For simplicity, using a nonce of zeros. Use random nonce in production.
-    let nonce = Nonce::assume_unique_for_key(nonce_bytes);
+    let nonce = Nonce::assume_unique_for_key([0u8; NONCE_LEN]);
     let mut in_out = data.to_vec();
-    in_out.extend_from_slice(&[0u8; aead::AES_256_GCM.tag_len()]);
-    key.seal_in_place_append_tag(nonce, aead::Aad::empty(), &mut in_out).unwrap();
-    in_out
+    key.open_in_place(nonce, aead::Aad::empty(), &mut in_out).ok().map(|pt| pt.to_vec())
 }
 
-fn decrypt_data(key_bytes: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+const NONCE_LEN: usize = 12;
+
+// STREAM-style chunked AEAD: the plaintext is sealed in fixed-size blocks
+// instead of one giant in-memory buffer, so neither side needs to hold the
+// whole vault in RAM. Each block's nonce is `STREAM_BASE_NONCE_LEN` random
+// bytes shared by the whole stream, followed by a 32-bit little-endian block
+// counter, with the nonce's final byte used as a last-block marker so a
+// stream truncated after a non-final block fails to decrypt instead of
+// silently accepting a partial vault.
+const CHUNK_SIZE: usize = 1024 * 1024;
+const STREAM_BASE_NONCE_LEN: usize = 7;
+const STREAM_LAST_BLOCK: u8 = 1;
+const STREAM_NOT_LAST_BLOCK: u8 = 0;
+
+fn stream_block_nonce(base_nonce: &[u8; STREAM_BASE_NONCE_LEN], counter: u32, is_last: bool) -> Nonce {
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    nonce_bytes[..STREAM_BASE_NONCE_LEN].copy_from_slice(base_nonce);
+    nonce_bytes[STREAM_BASE_NONCE_LEN..NONCE_LEN - 1].copy_from_slice(&counter.to_le_bytes());
+    nonce_bytes[NONCE_LEN - 1] = if is_last { STREAM_LAST_BLOCK } else { STREAM_NOT_LAST_BLOCK };
+    Nonce::assume_unique_for_key(nonce_bytes)
+}
+
+// Reads up to `buf.len()` bytes, looping until the reader is exhausted or
+// the buffer is full, since a single `Read::read` call is allowed to return
+// short even when more data is available.
+fn fill_buffer(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut total = 0;
+    while total < buf.len() {
+        let n = reader.read(&mut buf[total..])?;
+        if n == 0 {
+            break;
+        }
+        total += n;
+    }
+    Ok(total)
+}
+
+// Encrypts `reader` to `writer` as a sequence of length-prefixed, independently
+// authenticated `CHUNK_SIZE` blocks behind a single random base nonce.
+fn encrypt_reader_to_writer<R: Read, W: Write>(key_bytes: &[u8], mut reader: R, mut writer: W) -> std::io::Result<()> {
     let unbound_key = UnboundKey::new(&aead::AES_256_GCM, key_bytes).unwrap();
     let key = LessSafeKey::new(unbound_key);
-    let nonce_bytes = [0u8; 12]; // Must match encryption nonce
-    let nonce = Nonce::assume_unique_for_key(nonce_bytes);
-    let mut in_out = data.to_vec();
-    match key.open_in_place(nonce, aead::Aad::empty(), &mut in_out) {
-        Ok(plaintext) => Some(plaintext.to_vec()),
-        Err(_) => None,
+
+    let rng = rand::SystemRandom::new();
+    let mut base_nonce = [0u8; STREAM_BASE_NONCE_LEN];
+    rng.fill(&mut base_nonce).expect("failed to generate stream base nonce");
+    writer.write_all(&base_nonce)?;
+
+    let mut current = vec![0u8; CHUNK_SIZE];
+    let mut current_len = fill_buffer(&mut reader, &mut current)?;
+    let mut counter: u32 = 0;
+
+    loop {
+        let mut lookahead = vec![0u8; CHUNK_SIZE];
+        let lookahead_len = fill_buffer(&mut reader, &mut lookahead)?;
+        let is_last = lookahead_len == 0;
+
+        let nonce = stream_block_nonce(&base_nonce, counter, is_last);
+        let mut in_out = current[..current_len].to_vec();
+        key.seal_in_place_append_tag(nonce, aead::Aad::empty(), &mut in_out).unwrap();
+
+        writer.write_all(&(in_out.len() as u32).to_le_bytes())?;
+        writer.write_all(&in_out)?;
+
+        if is_last {
+            return Ok(());
+        }
+        current = lookahead;
+        current_len = lookahead_len;
+        counter += 1;
     }
 }
 
-fn load_passwords(master_password: &str) -> PasswordStore {
-    if let Ok(mut file) = File::open(STORAGE_FILE) {
-        let mut encrypted_data = Vec::new();
-        file.read_to_end(&mut encrypted_data).unwrap();
-        let key_bytes = generate_key_from_password(master_password);
-        if let Some(decrypted) = decrypt_data(&key_bytes, &encrypted_data) {
-            serde_json::from_slice(&decrypted).unwrap_or(PasswordStore { passwords: HashMap::new() })
-        } else {
-            println!("Failed to decrypt data. Possibly wrong password.");
-            std::process::exit(1);
+// Reads a 4-byte little-endian length prefix, returning `None` at a clean
+// EOF (no more blocks) and an error on a prefix cut short mid-read.
+fn read_block_len(reader: &mut impl Read) -> std::io::Result<Option<u32>> {
+    let mut len_bytes = [0u8; 4];
+    let mut total = 0;
+    while total < len_bytes.len() {
+        let n = reader.read(&mut len_bytes[total..])?;
+        if n == 0 {
+            if total == 0 {
+                return Ok(None);
+            }
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "truncated block length prefix"));
+        }
+        total += n;
+    }
+    Ok(Some(u32::from_le_bytes(len_bytes)))
+}
+
+// Decrypts a stream written by `encrypt_reader_to_writer`, verifying each
+// block's tag before writing its plaintext and before moving on to the
+// next block. A stream truncated right after a non-final block is detected
+// because the dangling block was sealed with the "not last" nonce flag but
+// looks like the last block on read, so its tag fails to verify.
+fn decrypt_reader_to_writer<R: Read, W: Write>(key_bytes: &[u8], mut reader: R, mut writer: W) -> Result<(), String> {
+    let unbound_key = UnboundKey::new(&aead::AES_256_GCM, key_bytes).map_err(|_| "invalid key".to_string())?;
+    let key = LessSafeKey::new(unbound_key);
+
+    let mut base_nonce = [0u8; STREAM_BASE_NONCE_LEN];
+    reader.read_exact(&mut base_nonce).map_err(|e| e.to_string())?;
+
+    let mut next_len = read_block_len(&mut reader).map_err(|e| e.to_string())?;
+    let mut counter: u32 = 0;
+
+    while let Some(block_len) = next_len {
+        if block_len as usize > CHUNK_SIZE + aead::AES_256_GCM.tag_len() {
+            return Err("block length prefix exceeds the maximum block size".to_string());
+        }
+        let mut block = vec![0u8; block_len as usize];
+        reader.read_exact(&mut block).map_err(|e| e.to_string())?;
+
+        next_len = read_block_len(&mut reader).map_err(|e| e.to_string())?;
+        let is_last = next_len.is_none();
+
+        let nonce = stream_block_nonce(&base_nonce, counter, is_last);
+        let plaintext = key
+            .open_in_place(nonce, aead::Aad::empty(), &mut block)
+            .map_err(|_| "failed to decrypt block (wrong password, corrupted, or truncated vault)".to_string())?;
+        writer.write_all(plaintext).map_err(|e| e.to_string())?;
+
+        counter += 1;
+    }
+
+    Ok(())
+}
+
+// In-memory convenience wrapper around the chunked stream for call sites
+// that already hold the whole ciphertext as a `Vec<u8>` (currently just
+// `verify_master_password`'s current-format branch) — the framing is
+// identical either way. Vaults predating this series need
+// `decrypt_data_legacy` instead; they don't have STREAM framing at all.
+fn decrypt_data(key_bytes: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    decrypt_reader_to_writer(key_bytes, data, &mut out).ok()?;
+    Some(out)
+}
+
+const HEADER_LEN: usize = 4 + 1 + SALT_LEN + 12;
+
+// Loads the vault, deriving the Argon2id key only once for the whole
+// session instead of on every save. Returns the key and header alongside
+// the store so the caller can reuse both via `save_passwords`. The header
+// is streamed straight off the file handle and the ciphertext that follows
+// it is decrypted straight out of the same handle, so the vault's on-disk
+// bytes are never buffered twice over.
+fn load_passwords(master_password: &str) -> (PasswordStore, [u8; 32], VaultHeader) {
+    match File::open(STORAGE_FILE) {
+        Ok(mut file) => {
+            let mut magic = [0u8; 4];
+            let magic_read = fill_buffer(&mut file, &mut magic).unwrap();
+
+            if magic_read == 4 && &magic == HEADER_MAGIC {
+                let mut rest_of_header = vec![0u8; HEADER_LEN - 4];
+                file.read_exact(&mut rest_of_header).expect("truncated vault header");
+                let mut header_bytes = Vec::with_capacity(HEADER_LEN);
+                header_bytes.extend_from_slice(&magic);
+                header_bytes.extend_from_slice(&rest_of_header);
+                let (header, _) = VaultHeader::parse(&header_bytes).expect("just-validated header failed to parse");
+
+                match generate_key_from_password(master_password, &header.salt, header.params) {
+                    Ok(key_bytes) => {
+                        let mut decrypted = Vec::new();
+                        match decrypt_reader_to_writer(&key_bytes, &mut file, &mut decrypted) {
+                            Ok(()) => {
+                                let store = serde_json::from_slice(&decrypted)
+                                    .unwrap_or(PasswordStore { passwords: HashMap::new() });
+                                (store, key_bytes, header)
+                            }
+                            Err(_) => {
+                                println!("Failed to decrypt data. Possibly wrong password.");
+                                std::process::exit(1);
+                            }
+                        }
+                    }
+                    Err(_) => {
+                        println!("Failed to decrypt data. Possibly wrong password.");
+                        std::process::exit(1);
+                    }
+                }
+            } else {
+                // Legacy SHA-256 vault (or a file too short to hold the magic): fall
+                // back to a single-shot decrypt of the whole file, then derive a
+                // fresh Argon2id key/header so the next save upgrades the format.
+                let mut rest = Vec::new();
+                file.read_to_end(&mut rest).unwrap();
+                let mut file_data = magic[..magic_read].to_vec();
+                file_data.extend_from_slice(&rest);
+
+                let legacy_key = generate_key_from_password_legacy(master_password);
+                match decrypt_data_legacy(&legacy_key, &file_data) {
+                    Some(decrypted) => {
+                        let store = serde_json::from_slice(&decrypted).unwrap_or(PasswordStore { passwords: HashMap::new() });
+                        let header = VaultHeader::generate(KdfParams::default());
+                        let key_bytes = generate_key_from_password(master_password, &header.salt, header.params)
+                            .expect("Argon2id key derivation failed for freshly generated parameters");
+                        (store, key_bytes, header)
+                    }
+                    None => {
+                        println!("Failed to decrypt data. Possibly wrong password.");
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        Err(_) => {
+            let header = VaultHeader::generate(KdfParams::default());
+            let key_bytes = generate_key_from_password(master_password, &header.salt, header.params)
+                .expect("Argon2id key derivation failed for freshly generated parameters");
+            (PasswordStore { passwords: HashMap::new() }, key_bytes, header)
         }
-    } else {
-        PasswordStore { passwords: HashMap::new() }
     }
 }
 
-fn save_passwords(store: &PasswordStore, master_password: &str) {
+// Writes the vault atomically (temp file plus rename), streaming the
+// ciphertext straight into the temp file instead of building it as one
+// in-memory buffer.
+fn write_vault(store: &PasswordStore, key_bytes: &[u8; 32], header: &VaultHeader) -> std::io::Result<()> {
+    let data = serde_json::to_vec(store).unwrap();
+    let tmp_path = format!("{}.tmp", STORAGE_FILE);
+    {
+        let mut tmp_file = File::create(&tmp_path)?;
+        tmp_file.write_all(&header.to_bytes())?;
+        encrypt_reader_to_writer(key_bytes, data.as_slice(), &mut tmp_file)?;
+        tmp_file.sync_all()?;
+    }
+    std::fs::rename(&tmp_path, STORAGE_FILE)
+}
+
+// Saves using an already-derived key and its existing header instead of
+// deriving a fresh one, so a salt/key cached for the session stays valid.
+// The salt is only ever rotated explicitly, by `rotate_master_password`.
+fn save_passwords(store: &PasswordStore, key_bytes: &[u8; 32], header: &VaultHeader) {
+    write_vault(store, key_bytes, header).unwrap();
+}
+
+// Keystore-v3-style backup format (modeled on the Ethereum web3 keystore),
+// so vaults can be moved between machines without relying on our opaque
+// `passwords.enc` layout. `kdfparams` mirrors `KdfParams` so a keystore can
+// be re-derived exactly; `mac` lets a wrong password be rejected before any
+// AEAD decryption is attempted.
+const KEYSTORE_VERSION: u32 = 3;
+
+#[derive(Serialize, Deserialize)]
+struct Keystore {
+    version: u32,
+    crypto: KeystoreCrypto,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeystoreCrypto {
+    cipher: String,
+    ciphertext: String,
+    cipherparams: KeystoreCipherParams,
+    kdf: String,
+    kdfparams: KeystoreKdfParams,
+    mac: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeystoreCipherParams {
+    nonce: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeystoreKdfParams {
+    salt: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+// Derives 64 bytes instead of the usual 32: the first half is the AES-256-GCM
+// key and the second half is the HMAC key used for the keystore's `mac`, so
+// a wrong password can be detected without ever touching the AEAD tag.
+// Returns `Err` instead of panicking so a hand-edited or corrupted
+// `kdfparams` in an imported backup can't crash the process; `export_keystore`
+// derives from its own freshly generated, trusted params and can `.expect()`.
+fn derive_keystore_key(password: &str, salt: &[u8], params: KdfParams) -> Result<[u8; 64], String> {
+    let argon2_params = Params::new(params.memory_kib, params.iterations, params.parallelism, Some(64))
+        .map_err(|e| e.to_string())?;
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, argon2_params);
+    let mut key = [0u8; 64];
+    argon2
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+fn export_keystore(store: &PasswordStore, password: &str, path: &str) -> std::io::Result<()> {
     let data = serde_json::to_vec(store).unwrap();
-    let key_bytes = generate_key_from_password(master_password);
-    let encrypted = encrypt_data(&key_bytes, &data);
-    let mut file = File::create(STORAGE_FILE).unwrap();
-    file.write_all(&encrypted).unwrap();
+
+    let header = VaultHeader::generate(KdfParams::default());
+    let derived = derive_keystore_key(password, &header.salt, header.params)
+        .expect("Argon2id key derivation failed for freshly generated parameters");
+    let (enc_key, mac_key) = derived.split_at(32);
+
+    let unbound_key = UnboundKey::new(&aead::AES_256_GCM, enc_key).unwrap();
+    let key = LessSafeKey::new(unbound_key);
+    let rng = rand::SystemRandom::new();
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    rng.fill(&mut nonce_bytes).expect("failed to generate nonce");
+    let nonce = Nonce::assume_unique_for_key(nonce_bytes);
+
+    let mut ciphertext = data;
+    key.seal_in_place_append_tag(nonce, aead::Aad::empty(), &mut ciphertext).unwrap();
+
+    let mut mac = HmacSha256::new_from_slice(mac_key).expect("HMAC accepts a key of any size");
+    mac.update(&ciphertext);
+    let mac_bytes = mac.finalize().into_bytes();
+
+    let keystore = Keystore {
+        version: KEYSTORE_VERSION,
+        crypto: KeystoreCrypto {
+            cipher: "aes-256-gcm".to_string(),
+            ciphertext: encode(&ciphertext),
+            cipherparams: KeystoreCipherParams { nonce: encode(nonce_bytes) },
+            kdf: "argon2id".to_string(),
+            kdfparams: KeystoreKdfParams {
+                salt: encode(header.salt),
+                memory_kib: header.params.memory_kib,
+                iterations: header.params.iterations,
+                parallelism: header.params.parallelism,
+            },
+            mac: hex::encode(mac_bytes),
+        },
+    };
+
+    let json = serde_json::to_string_pretty(&keystore).unwrap();
+    let mut file = File::create(path)?;
+    file.write_all(json.as_bytes())
+}
+
+fn import_keystore(path: &str, password: &str) -> Result<PasswordStore, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).map_err(|e| e.to_string())?;
+    let keystore: Keystore = serde_json::from_str(&contents).map_err(|e| e.to_string())?;
+
+    let salt = decode(&keystore.crypto.kdfparams.salt).map_err(|e| e.to_string())?;
+    let params = KdfParams {
+        memory_kib: keystore.crypto.kdfparams.memory_kib,
+        iterations: keystore.crypto.kdfparams.iterations,
+        parallelism: keystore.crypto.kdfparams.parallelism,
+    };
+    let derived = derive_keystore_key(password, &salt, params).map_err(|_| "malformed keystore: invalid kdfparams".to_string())?;
+    let (enc_key, mac_key) = derived.split_at(32);
+
+    let ciphertext = decode(&keystore.crypto.ciphertext).map_err(|e| e.to_string())?;
+
+    let mut mac = HmacSha256::new_from_slice(mac_key).expect("HMAC accepts a key of any size");
+    mac.update(&ciphertext);
+    let actual_mac = hex::decode(&keystore.crypto.mac).map_err(|e| e.to_string())?;
+    if mac.verify_slice(&actual_mac).is_err() {
+        return Err("invalid password".to_string());
+    }
+
+    let nonce_bytes = decode(&keystore.crypto.cipherparams.nonce).map_err(|e| e.to_string())?;
+    if nonce_bytes.len() != NONCE_LEN {
+        return Err("malformed keystore: wrong nonce length".to_string());
+    }
+    let nonce = Nonce::assume_unique_for_key(nonce_bytes.try_into().unwrap());
+    let unbound_key = UnboundKey::new(&aead::AES_256_GCM, enc_key).map_err(|_| "invalid key".to_string())?;
+    let key = LessSafeKey::new(unbound_key);
+    let mut in_out = ciphertext;
+    let plaintext = key
+        .open_in_place(nonce, aead::Aad::empty(), &mut in_out)
+        .map_err(|_| "decryption failed".to_string())?;
+
+    serde_json::from_slice(plaintext).map_err(|e| e.to_string())
+}
+
+const KEYRING_SERVICE: &str = "pass-manager";
+const KEYRING_ACCOUNT: &str = "master-key";
+
+fn keyring_entry() -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_ACCOUNT)
+}
+
+// Tracks whether the current session's key is held in the OS keyring:
+// `CachedKey` once it was pulled from or stored in the keyring, `Password`
+// when it was typed in this run or has been forgotten from the keyring. Both
+// variants carry the key and header derived once at unlock, so saves never
+// re-derive or rotate the salt — only `rotate_master_password` does that. The
+// distinction matters for option 10 (rotate password): whether the OS
+// keyring already holds a key at all — always for a `CachedKey` session, and
+// possibly for a `Password` session too — decides whether the rotated key
+// replaces that keyring entry and the session becomes `CachedKey`, or the
+// keyring is left untouched and the session stays `Password`.
+enum UnlockState {
+    Password([u8; 32], VaultHeader),
+    CachedKey([u8; 32], VaultHeader),
+}
+
+impl UnlockState {
+    fn key_and_header(&self) -> (&[u8; 32], &VaultHeader) {
+        match self {
+            UnlockState::Password(key_bytes, header) => (key_bytes, header),
+            UnlockState::CachedKey(key_bytes, header) => (key_bytes, header),
+        }
+    }
+}
+
+fn persist(store: &PasswordStore, unlock: &UnlockState) {
+    let (key_bytes, header) = unlock.key_and_header();
+    save_passwords(store, key_bytes, header);
+}
+
+// Checks whether `password` can decrypt the current vault without actually
+// replacing the in-memory store, so a password-rotation flow can confirm
+// the caller knows the current password before prompting for a new one.
+fn verify_master_password(password: &str) -> bool {
+    match std::fs::read(STORAGE_FILE) {
+        Ok(file_data) => match VaultHeader::parse(&file_data) {
+            Some((header, ciphertext)) => generate_key_from_password(password, &header.salt, header.params)
+                .ok()
+                .and_then(|key_bytes| decrypt_data(&key_bytes, ciphertext))
+                .is_some(),
+            None => decrypt_data_legacy(&generate_key_from_password_legacy(password), &file_data).is_some(),
+        },
+        Err(_) => false,
+    }
+}
+
+// Re-encrypts `store` under a freshly derived key and salt and atomically
+// replaces `passwords.enc`, so a crash mid-rotation can't corrupt or lose
+// the vault. Returns the new key and header for the caller to adopt.
+fn rotate_master_password(store: &PasswordStore, new_password: &str) -> std::io::Result<([u8; 32], VaultHeader)> {
+    let header = VaultHeader::generate(KdfParams::default());
+    let key_bytes = generate_key_from_password(new_password, &header.salt, header.params)
+        .expect("Argon2id key derivation failed for freshly generated parameters");
+    write_vault(store, &key_bytes, &header)?;
+    Ok((key_bytes, header))
+}
+
+// Looks up a previously-stashed derived key so the vault can be unlocked
+// without prompting for the master password. Returns `None` on any failure
+// (no entry, locked keyring, corrupt value) so the caller falls back to the
+// normal password prompt.
+fn load_key_from_keyring() -> Option<[u8; 32]> {
+    let entry = keyring_entry().ok()?;
+    let encoded = entry.get_password().ok()?;
+    decode(encoded).ok()?.try_into().ok()
+}
+
+// Like `load_passwords`, but for the OS-keyring path where the key is already
+// known and doesn't need deriving from a password. Streams the ciphertext
+// straight out of the file handle. Returns `None` on any failure (no vault,
+// legacy format, wrong key) so the caller falls back to the password prompt.
+fn try_unlock_with_cached_key(key_bytes: [u8; 32]) -> Option<(PasswordStore, VaultHeader)> {
+    let mut file = File::open(STORAGE_FILE).ok()?;
+    let mut header_bytes = vec![0u8; HEADER_LEN];
+    file.read_exact(&mut header_bytes).ok()?;
+    let (header, _) = VaultHeader::parse(&header_bytes)?;
+
+    let mut decrypted = Vec::new();
+    decrypt_reader_to_writer(&key_bytes, &mut file, &mut decrypted).ok()?;
+    let store = serde_json::from_slice(&decrypted).ok()?;
+    Some((store, header))
+}
+
+fn store_key_in_keyring(key_bytes: &[u8; 32]) -> keyring::Result<()> {
+    keyring_entry()?.set_password(&encode(key_bytes))
+}
+
+fn forget_key_in_keyring() -> keyring::Result<()> {
+    keyring_entry()?.delete_credential()
 }
 
 fn main() {
     println!("Simple Rust Password Manager");
-    println!("Enter your master password:");
-    let master_password = read_password().unwrap();
 
-    let mut store = load_passwords(&master_password);
+    let mut unlock_state = None;
+    let mut store = None;
+
+    if let Some(key_bytes) = load_key_from_keyring() {
+        if let Some((cached_store, header)) = try_unlock_with_cached_key(key_bytes) {
+            println!("Unlocked using the OS keyring.");
+            store = Some(cached_store);
+            unlock_state = Some(UnlockState::CachedKey(key_bytes, header));
+        } else {
+            println!("Stored keyring key could not unlock the vault.");
+        }
+    }
+
+    let (mut store, mut unlock_state) = match (store, unlock_state) {
+        (Some(store), Some(unlock_state)) => (store, unlock_state),
+        _ => {
+            println!("Enter your master password:");
+            let master_password = read_password().unwrap();
+            let (store, key_bytes, header) = load_passwords(&master_password);
+            (store, UnlockState::Password(key_bytes, header))
+        }
+    };
 
     loop {
         println!("\nOptions:");
         println!("1. Add password");
         println!("2. Retrieve password");
         println!("3. List entries");
-        println!("4. Exit");
+        println!("4. Update entry");
+        println!("5. Delete entry");
+        println!("6. Export keystore backup");
+        println!("7. Import keystore backup");
+        println!("8. Store master key in OS keyring");
+        println!("9. Forget master key from OS keyring");
+        println!("10. Change master password");
+        println!("11. Exit");
         println!("Choose an option:");
 
         let mut choice = String::new();
@@ -94,11 +679,33 @@ fn main() {
                 let mut account = String::new();
                 std::io::stdin().read_line(&mut account).unwrap();
 
+                println!("Enter username:");
+                let mut username = String::new();
+                std::io::stdin().read_line(&mut username).unwrap();
+
                 println!("Enter password:");
                 let password = read_password().unwrap();
 
-                store.passwords.insert(account.trim().to_string(), password);
-                save_passwords(&store, &master_password);
+                println!("Enter URL (optional, press Enter to skip):");
+                let mut url = String::new();
+                std::io::stdin().read_line(&mut url).unwrap();
+
+                println!("Enter notes (optional, press Enter to skip):");
+                let mut notes = String::new();
+                std::io::stdin().read_line(&mut notes).unwrap();
+
+                let now = current_unix_timestamp();
+                let entry = PasswordEntry {
+                    username: username.trim().to_string(),
+                    password,
+                    url: if url.trim().is_empty() { None } else { Some(url.trim().to_string()) },
+                    notes: if notes.trim().is_empty() { None } else { Some(notes.trim().to_string()) },
+                    created_at: now,
+                    modified_at: now,
+                };
+
+                store.passwords.insert(account.trim().to_string(), entry);
+                persist(&store, &unlock_state);
                 println!("Password saved.");
             }
             "2" => {
@@ -107,17 +714,179 @@ fn main() {
                 std::io::stdin().read_line(&mut account).unwrap();
 
                 match store.passwords.get(account.trim()) {
-                    Some(pw) => println!("Password: {}", pw),
+                    Some(entry) => {
+                        println!("Username: {}", entry.username);
+                        println!("Password: {}", entry.password);
+                        if let Some(url) = &entry.url {
+                            println!("URL: {}", url);
+                        }
+                        if let Some(notes) = &entry.notes {
+                            println!("Notes: {}", notes);
+                        }
+                        println!("Created: {}", entry.created_at);
+                        println!("Modified: {}", entry.modified_at);
+                    }
                     None => println!("No entry found for that account."),
                 }
             }
             "3" => {
                 println!("Stored accounts:");
-                for account in store.passwords.keys() {
-                    println!("- {}", account);
+                for (account, entry) in &store.passwords {
+                    println!("- {} (created {}, modified {})", account, entry.created_at, entry.modified_at);
                 }
             }
             "4" => {
+                println!("Enter account name to update:");
+                let mut account = String::new();
+                std::io::stdin().read_line(&mut account).unwrap();
+
+                match store.passwords.get(account.trim()).cloned() {
+                    Some(existing) => {
+                        println!("Enter new username (leave blank to keep \"{}\"):", existing.username);
+                        let mut username_input = String::new();
+                        std::io::stdin().read_line(&mut username_input).unwrap();
+                        let username = if username_input.trim().is_empty() {
+                            existing.username
+                        } else {
+                            username_input.trim().to_string()
+                        };
+
+                        println!("Enter new password (leave blank to keep current):");
+                        let password_input = read_password().unwrap();
+                        let password = if password_input.is_empty() { existing.password } else { password_input };
+
+                        println!("Enter new URL (leave blank to keep current, '-' to clear):");
+                        let mut url_input = String::new();
+                        std::io::stdin().read_line(&mut url_input).unwrap();
+                        let url = match url_input.trim() {
+                            "" => existing.url,
+                            "-" => None,
+                            other => Some(other.to_string()),
+                        };
+
+                        println!("Enter new notes (leave blank to keep current, '-' to clear):");
+                        let mut notes_input = String::new();
+                        std::io::stdin().read_line(&mut notes_input).unwrap();
+                        let notes = match notes_input.trim() {
+                            "" => existing.notes,
+                            "-" => None,
+                            other => Some(other.to_string()),
+                        };
+
+                        let updated = PasswordEntry {
+                            username,
+                            password,
+                            url,
+                            notes,
+                            created_at: existing.created_at,
+                            modified_at: current_unix_timestamp(),
+                        };
+
+                        store.passwords.insert(account.trim().to_string(), updated);
+                        persist(&store, &unlock_state);
+                        println!("Entry updated.");
+                    }
+                    None => println!("No entry found for that account."),
+                }
+            }
+            "5" => {
+                println!("Enter account name to delete:");
+                let mut account = String::new();
+                std::io::stdin().read_line(&mut account).unwrap();
+
+                if store.passwords.remove(account.trim()).is_some() {
+                    persist(&store, &unlock_state);
+                    println!("Entry deleted.");
+                } else {
+                    println!("No entry found for that account.");
+                }
+            }
+            "6" => {
+                println!("Enter path for keystore backup:");
+                let mut path = String::new();
+                std::io::stdin().read_line(&mut path).unwrap();
+
+                println!("Enter a password to protect the backup:");
+                let backup_password = read_password().unwrap();
+
+                match export_keystore(&store, &backup_password, path.trim()) {
+                    Ok(()) => println!("Keystore backup written."),
+                    Err(e) => println!("Failed to write keystore backup: {}", e),
+                }
+            }
+            "7" => {
+                println!("Enter path of keystore backup to import:");
+                let mut path = String::new();
+                std::io::stdin().read_line(&mut path).unwrap();
+
+                println!("Enter the backup's password:");
+                let backup_password = read_password().unwrap();
+
+                match import_keystore(path.trim(), &backup_password) {
+                    Ok(imported) => {
+                        store.passwords.extend(imported.passwords);
+                        persist(&store, &unlock_state);
+                        println!("Keystore backup imported.");
+                    }
+                    Err(e) => println!("Failed to import keystore backup: {}", e),
+                }
+            }
+            "8" => {
+                let (key_bytes, header) = unlock_state.key_and_header();
+                let (key_bytes, header) = (*key_bytes, *header);
+                match store_key_in_keyring(&key_bytes) {
+                    Ok(()) => {
+                        println!("Master key stored in OS keyring.");
+                        unlock_state = UnlockState::CachedKey(key_bytes, header);
+                    }
+                    Err(e) => println!("Failed to store key in keyring: {}", e),
+                }
+            }
+            "9" => match forget_key_in_keyring() {
+                Ok(()) => {
+                    println!("Master key removed from OS keyring.");
+                    let (key_bytes, header) = unlock_state.key_and_header();
+                    let (key_bytes, header) = (*key_bytes, *header);
+                    unlock_state = UnlockState::Password(key_bytes, header);
+                }
+                Err(e) => println!("Failed to remove key from keyring: {}", e),
+            },
+            "10" => {
+                println!("Enter current master password:");
+                let current_password = read_password().unwrap();
+
+                if !verify_master_password(&current_password) {
+                    println!("Incorrect current password.");
+                } else {
+                    println!("Enter new master password:");
+                    let new_password = read_password().unwrap();
+                    println!("Confirm new master password:");
+                    let confirm_password = read_password().unwrap();
+
+                    if new_password != confirm_password {
+                        println!("New passwords did not match.");
+                    } else {
+                        let keyring_enabled =
+                            matches!(unlock_state, UnlockState::CachedKey(..)) || load_key_from_keyring().is_some();
+
+                        match rotate_master_password(&store, &new_password) {
+                            Ok((key_bytes, header)) => {
+                                if keyring_enabled {
+                                    if let Err(e) = store_key_in_keyring(&key_bytes) {
+                                        println!("Failed to update keyring entry: {}", e);
+                                    }
+                                    unlock_state = UnlockState::CachedKey(key_bytes, header);
+                                } else {
+                                    unlock_state = UnlockState::Password(key_bytes, header);
+                                }
+                                println!("Master password changed.");
+                            }
+                            Err(e) => println!("Failed to rotate master password: {}", e),
+                        }
+                    }
+                }
+            }
+            "11" => {
                 println!("Goodbye!");
                 break;
             }
@@ -125,3 +894,244 @@ fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn test_key() -> [u8; 32] {
+        generate_key_from_password("correct horse battery staple", b"0123456789abcdef", KdfParams::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn stream_round_trip_across_multiple_blocks() {
+        let key = test_key();
+        // A few bytes over two chunk boundaries so the block-counter/last-block
+        // handling actually gets exercised, not just the single-block case.
+        let plaintext = vec![7u8; CHUNK_SIZE * 2 + 100];
+
+        let mut ciphertext = Vec::new();
+        encrypt_reader_to_writer(&key, plaintext.as_slice(), &mut ciphertext).unwrap();
+
+        let mut decrypted = Vec::new();
+        decrypt_reader_to_writer(&key, ciphertext.as_slice(), &mut decrypted).unwrap();
+
+        assert_eq!(decrypted, plaintext);
+    }
+
+    #[test]
+    fn stream_detects_truncation() {
+        let key = test_key();
+        let plaintext = vec![3u8; CHUNK_SIZE + 100];
+
+        let mut ciphertext = Vec::new();
+        encrypt_reader_to_writer(&key, plaintext.as_slice(), &mut ciphertext).unwrap();
+
+        let truncated = &ciphertext[..ciphertext.len() - 1];
+        let mut decrypted = Vec::new();
+        assert!(decrypt_reader_to_writer(&key, truncated, &mut decrypted).is_err());
+    }
+
+    #[test]
+    fn stream_rejects_wrong_password_and_tampered_ciphertext() {
+        let key = test_key();
+        let wrong_key =
+            generate_key_from_password("a different password entirely", b"0123456789abcdef", KdfParams::default())
+                .unwrap();
+        let plaintext = b"hunter2".to_vec();
+
+        let mut ciphertext = Vec::new();
+        encrypt_reader_to_writer(&key, plaintext.as_slice(), &mut ciphertext).unwrap();
+
+        let mut decrypted = Vec::new();
+        assert!(decrypt_reader_to_writer(&wrong_key, ciphertext.as_slice(), &mut decrypted).is_err());
+
+        let mut tampered = ciphertext.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 0xff;
+        let mut decrypted = Vec::new();
+        assert!(decrypt_reader_to_writer(&key, tampered.as_slice(), &mut decrypted).is_err());
+    }
+
+    #[test]
+    fn stream_rejects_out_of_range_block_length() {
+        let key = test_key();
+        let plaintext = b"short vault".to_vec();
+
+        let mut ciphertext = Vec::new();
+        encrypt_reader_to_writer(&key, plaintext.as_slice(), &mut ciphertext).unwrap();
+
+        // Corrupt the length prefix that follows the base nonce so it claims a
+        // block far larger than any real chunk could be.
+        let len_offset = STREAM_BASE_NONCE_LEN;
+        ciphertext[len_offset..len_offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let mut decrypted = Vec::new();
+        let err = decrypt_reader_to_writer(&key, ciphertext.as_slice(), &mut decrypted).unwrap_err();
+        assert!(err.contains("maximum block size"));
+    }
+
+    // Seals `data` the way the pre-series `encrypt_data` did: a single
+    // `seal_in_place_append_tag` call under an all-zero nonce, no framing.
+    fn seal_legacy(key_bytes: &[u8], data: &[u8]) -> Vec<u8> {
+        let unbound_key = UnboundKey::new(&aead::AES_256_GCM, key_bytes).unwrap();
+        let key = LessSafeKey::new(unbound_key);
+        let nonce = Nonce::assume_unique_for_key([0u8; NONCE_LEN]);
+        let mut in_out = data.to_vec();
+        key.seal_in_place_append_tag(nonce, aead::Aad::empty(), &mut in_out).unwrap();
+        in_out
+    }
+
+    #[test]
+    fn legacy_vault_decrypts_and_upgrades_to_stream_format() {
+        let legacy_key = generate_key_from_password_legacy("hunter2");
+        let plaintext = br#"{"passwords":{}}"#.to_vec();
+        let legacy_ciphertext = seal_legacy(&legacy_key, &plaintext);
+
+        let recovered = decrypt_data_legacy(&legacy_key, &legacy_ciphertext).expect("legacy vault should decrypt");
+        assert_eq!(recovered, plaintext);
+
+        // The upgrade path: the next save re-encrypts the recovered plaintext
+        // under a fresh Argon2id key and the current stream framing.
+        let header = VaultHeader::generate(KdfParams::default());
+        let new_key = generate_key_from_password("hunter2", &header.salt, header.params).unwrap();
+        let mut upgraded = Vec::new();
+        encrypt_reader_to_writer(&new_key, recovered.as_slice(), &mut upgraded).unwrap();
+
+        let mut roundtripped = Vec::new();
+        decrypt_reader_to_writer(&new_key, upgraded.as_slice(), &mut roundtripped).unwrap();
+        assert_eq!(roundtripped, plaintext);
+    }
+
+    fn sample_store() -> PasswordStore {
+        let mut passwords = HashMap::new();
+        passwords.insert(
+            "example.com".to_string(),
+            PasswordEntry {
+                username: "alice".to_string(),
+                password: "s3cret".to_string(),
+                url: None,
+                notes: None,
+                created_at: current_unix_timestamp(),
+                modified_at: current_unix_timestamp(),
+            },
+        );
+        PasswordStore { passwords }
+    }
+
+    // A unique path under the system temp dir, since these tests don't go
+    // through `STORAGE_FILE` and can just take an explicit path.
+    fn temp_path(label: &str) -> String {
+        let mut path = std::env::temp_dir();
+        path.push(format!("pass-manager-test-{}-{}-{:?}", label, std::process::id(), std::thread::current().id()));
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn keystore_export_import_round_trip() {
+        let store = sample_store();
+        let path = temp_path("keystore-round-trip");
+
+        export_keystore(&store, "keystore-password", &path).unwrap();
+        let imported = import_keystore(&path, "keystore-password").unwrap();
+
+        assert_eq!(imported.passwords["example.com"].username, "alice");
+        assert_eq!(imported.passwords["example.com"].password, "s3cret");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn keystore_import_rejects_wrong_password() {
+        let store = sample_store();
+        let path = temp_path("keystore-wrong-password");
+
+        export_keystore(&store, "correct-password", &path).unwrap();
+        let err = match import_keystore(&path, "incorrect-password") {
+            Err(e) => e,
+            Ok(_) => panic!("import should have failed"),
+        };
+
+        assert_eq!(err, "invalid password");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn keystore_import_rejects_tampered_ciphertext() {
+        let store = sample_store();
+        let path = temp_path("keystore-tampered");
+
+        export_keystore(&store, "keystore-password", &path).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let mut keystore: Keystore = serde_json::from_str(&contents).unwrap();
+        let mut ciphertext = decode(&keystore.crypto.ciphertext).unwrap();
+        let last = ciphertext.len() - 1;
+        ciphertext[last] ^= 0xff;
+        keystore.crypto.ciphertext = encode(&ciphertext);
+        std::fs::write(&path, serde_json::to_string_pretty(&keystore).unwrap()).unwrap();
+
+        let err = match import_keystore(&path, "keystore-password") {
+            Err(e) => e,
+            Ok(_) => panic!("import should have failed"),
+        };
+
+        assert_eq!(err, "invalid password");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    // Serializes the tests below that depend on the process's current
+    // directory, since `STORAGE_FILE` is a path relative to it.
+    static CWD_LOCK: Mutex<()> = Mutex::new(());
+
+    struct CwdGuard {
+        original: std::path::PathBuf,
+        _lock: std::sync::MutexGuard<'static, ()>,
+    }
+
+    impl CwdGuard {
+        fn enter_temp_dir(label: &str) -> Self {
+            let lock = CWD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            let original = std::env::current_dir().unwrap();
+            let mut dir = std::env::temp_dir();
+            dir.push(format!("pass-manager-test-{}-{}-{:?}", label, std::process::id(), std::thread::current().id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::env::set_current_dir(&dir).unwrap();
+            CwdGuard { original, _lock: lock }
+        }
+    }
+
+    impl Drop for CwdGuard {
+        fn drop(&mut self) {
+            let _ = std::env::set_current_dir(&self.original);
+        }
+    }
+
+    #[test]
+    fn rotate_master_password_round_trip() {
+        let _guard = CwdGuard::enter_temp_dir("rotate");
+
+        let (_, key_bytes, header) = load_passwords("old-password");
+        let store = sample_store();
+        save_passwords(&store, &key_bytes, &header);
+
+        assert!(verify_master_password("old-password"));
+        assert!(!verify_master_password("wrong-password"));
+
+        let (new_key, new_header) = rotate_master_password(&store, "new-password").unwrap();
+
+        assert!(!verify_master_password("old-password"));
+        assert!(verify_master_password("new-password"));
+
+        let (loaded_store, loaded_key, loaded_header) = load_passwords("new-password");
+        assert_eq!(loaded_store.passwords["example.com"].username, "alice");
+        assert_eq!(loaded_key, new_key);
+        assert_eq!(loaded_header.salt, new_header.salt);
+
+        let (cached_store, cached_header) =
+            try_unlock_with_cached_key(new_key).expect("cached key should unlock the rotated vault");
+        assert_eq!(cached_store.passwords["example.com"].username, "alice");
+        assert_eq!(cached_header.salt, new_header.salt);
+    }
+}